@@ -15,7 +15,7 @@
 use jj_cli::cli_util::CliRunner;
 use jj_cli::commit_templater::{CommitTemplateBuildFnTable, CommitTemplateLanguageExtension};
 use jj_cli::template_builder::TemplateLanguage;
-use jj_cli::template_parser::{self, TemplateParseError};
+use jj_cli::template_parser::{self, ExpressionNode, TemplateParseError};
 use jj_cli::templater::{TemplateFunction, TemplatePropertyError};
 use jj_lib::backend::CommitId;
 use jj_lib::commit::Commit;
@@ -24,6 +24,11 @@ use jj_lib::object_id::ObjectId;
 use jj_lib::repo::Repo;
 use jj_lib::revset::RevsetExpression;
 use once_cell::sync::OnceCell;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+use std::rc::Rc;
+use tracing::instrument;
 
 struct HexCounter;
 
@@ -37,16 +42,122 @@ fn num_digits_in_id(id: &CommitId) -> i64 {
     count
 }
 
-fn num_char_in_id(commit: Commit, ch_match: char) -> Result<i64, TemplatePropertyError> {
-    let mut count = 0;
-    for ch in commit.id().hex().chars() {
-        if ch == ch_match {
-            count += 1;
+/// Maps `f` over every commit in the repo in parallel, returning the
+/// maximum of the per-commit results (`None` when there are no commits).
+/// The results are combined with `max`, so the outcome does not depend on
+/// the order in which commits are visited; an error from `f` short-circuits
+/// the remaining work and is returned.
+///
+/// Example-local until `CommitTemplateLanguage` grows an equivalent helper.
+#[instrument(skip_all, fields(commits_visited = tracing::field::Empty))]
+fn par_map_all<T, F>(repo: &dyn Repo, f: F) -> Result<Option<T>, TemplatePropertyError>
+where
+    T: Ord + Send,
+    F: Fn(&CommitId) -> Result<T, TemplatePropertyError> + Sync,
+{
+    let commit_ids: Vec<CommitId> = RevsetExpression::all()
+        .evaluate_programmatic(repo)
+        .unwrap()
+        .iter()
+        .collect();
+    tracing::Span::current().record("commits_visited", commit_ids.len());
+    commit_ids
+        .par_iter()
+        .map(f)
+        .try_fold(|| None, |acc, result| Ok(std::cmp::max(acc, Some(result?))))
+        .try_reduce(|| None, |a, b| Ok(std::cmp::max(a, b)))
+}
+
+/// Byte offsets of every non-overlapping occurrence of `needle` within the
+/// commit's hex id. `needle` is a literal substring; pattern/regex matching
+/// is not implemented.
+fn match_positions_in_id(commit: &Commit, needle: &str) -> Vec<i64> {
+    commit
+        .id()
+        .hex()
+        .match_indices(needle)
+        .map(|(pos, _)| pos as i64)
+        .collect()
+}
+
+/// Validates a template argument as a non-empty substring literal, shared by
+/// both `num_char_in_id` and `positions_of_char_in_id` so the two methods
+/// reject the same inputs with the same message.
+fn expect_nonempty_substring_arg(node: &ExpressionNode<'_>) -> Result<Rc<str>, TemplateParseError> {
+    template_parser::expect_string_literal_with(node, |string, span| {
+        if string.is_empty() {
+            return Err(TemplateParseError::unexpected_expression(
+                "Expected a non-empty substring argument",
+                span,
+            ));
+        }
+        Ok(Rc::from(string))
+    })
+}
+
+fn num_char_in_id(commit: Commit, needle: Rc<str>) -> Result<i64, TemplatePropertyError> {
+    Ok(match_positions_in_id(&commit, &needle).len() as i64)
+}
+
+fn positions_of_char_in_id(
+    commit: Commit,
+    needle: Rc<str>,
+) -> Result<Vec<i64>, TemplatePropertyError> {
+    Ok(match_positions_in_id(&commit, &needle))
+}
+
+/// A JSON sidecar cache for a single value, keyed by the operation id it was
+/// computed at so it's invalidated whenever repo state changes. Generic over
+/// the cached value, but still example-local: `jj_cli` has no framework
+/// support for this in `build_cache_extensions`/`cache_extension` yet.
+struct OpKeyedCache<T> {
+    file_name: &'static str,
+    _value: std::marker::PhantomData<T>,
+}
+
+#[derive(Serialize)]
+struct OpKeyedCacheEntryRef<'a, T> {
+    op_id: &'a str,
+    value: &'a T,
+}
+
+#[derive(Deserialize)]
+struct OpKeyedCacheEntryOwned<T> {
+    op_id: String,
+    value: T,
+}
+
+impl<T> OpKeyedCache<T>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    const fn new(file_name: &'static str) -> Self {
+        Self {
+            file_name,
+            _value: std::marker::PhantomData,
+        }
+    }
+
+    fn path(&self, repo: &dyn Repo) -> std::path::PathBuf {
+        repo.repo_path().join(self.file_name)
+    }
+
+    fn load(&self, repo: &dyn Repo, op_id: &str) -> Option<T> {
+        let contents = std::fs::read_to_string(self.path(repo)).ok()?;
+        let entry: OpKeyedCacheEntryOwned<T> = serde_json::from_str(&contents).ok()?;
+        (entry.op_id == op_id).then_some(entry.value)
+    }
+
+    fn store(&self, repo: &dyn Repo, op_id: &str, value: &T) {
+        let entry = OpKeyedCacheEntryRef { op_id, value };
+        if let Ok(json) = serde_json::to_string(&entry) {
+            let _ = std::fs::write(self.path(repo), json);
         }
     }
-    Ok(count)
 }
 
+const MOST_DIGITS_CACHE: OpKeyedCache<i64> = OpKeyedCache::new("most_digits_in_id_cache.json");
+
 struct MostDigitsInId {
     count: OnceCell<i64>,
 }
@@ -58,32 +169,48 @@ impl MostDigitsInId {
         }
     }
 
-    fn count(&self, repo: &dyn Repo) -> i64 {
-        *self.count.get_or_init(|| {
-            RevsetExpression::all()
-                .evaluate_programmatic(repo)
-                .unwrap()
-                .iter()
-                .map(|id| num_digits_in_id(&id))
-                .max()
-                .unwrap_or(0)
-        })
+    #[instrument(skip_all)]
+    fn count(&self, repo: &dyn Repo) -> Result<i64, TemplatePropertyError> {
+        self.count
+            .get_or_try_init(|| {
+                let op_id = repo.op_id().hex();
+                if let Some(count) = MOST_DIGITS_CACHE.load(repo, &op_id) {
+                    tracing::debug!(op_id, count, "most_digits_in_id cache hit");
+                    return Ok(count);
+                }
+                // Propagate the first error from a per-commit extraction
+                // instead of unwrapping and panicking on it.
+                let count = par_map_all(repo, |id| Ok(num_digits_in_id(id)))?.unwrap_or(0);
+                MOST_DIGITS_CACHE.store(repo, &op_id, &count);
+                tracing::debug!(op_id, count, "most_digits_in_id cache miss, recomputed");
+                Ok(count)
+            })
+            .map(|count| *count)
     }
 }
 
+// Not implemented: a `most_digits()` revset function needs a `RevsetExtension`
+// surface on `CliRunner`, which doesn't exist in `jj_cli` yet. Blocked on that
+// upstream groundwork.
+
 impl CommitTemplateLanguageExtension for HexCounter {
+    #[instrument(skip_all)]
     fn build_fn_table<'repo>(&self) -> CommitTemplateBuildFnTable<'repo> {
         let mut table = CommitTemplateBuildFnTable::empty();
         table.commit_methods.insert(
             "has_most_digits",
             |language, _build_context, property, call| {
                 template_parser::expect_no_arguments(call)?;
-                let most_digits = language
-                    .cache_extension::<MostDigitsInId>()
-                    .unwrap()
-                    .count(language.repo());
+                // `count()` returns `Result<i64, TemplatePropertyError>`, which
+                // doesn't match this build closure's `TemplateParseError`
+                // result, so the lookup (and its error) is deferred to the
+                // per-commit closure below, where `TemplatePropertyError` is
+                // already the right type.
+                let most_digits_in_id = language.cache_extension::<MostDigitsInId>().unwrap();
+                let repo = language.repo();
                 Ok(
                     language.wrap_boolean(TemplateFunction::new(property, move |commit| {
+                        let most_digits = most_digits_in_id.count(repo)?;
                         Ok(num_digits_in_id(commit.id()) == most_digits)
                     })),
                 )
@@ -104,21 +231,24 @@ impl CommitTemplateLanguageExtension for HexCounter {
             "num_char_in_id",
             |language, _build_context, property, call| {
                 let [string_arg] = template_parser::expect_exact_arguments(call)?;
-                let char_arg =
-                    template_parser::expect_string_literal_with(string_arg, |string, span| {
-                        let chars: Vec<_> = string.chars().collect();
-                        match chars[..] {
-                            [ch] => Ok(ch),
-                            _ => Err(TemplateParseError::unexpected_expression(
-                                "Expected singular character argument",
-                                span,
-                            )),
-                        }
-                    })?;
+                let needle = expect_nonempty_substring_arg(string_arg)?;
 
                 Ok(
                     language.wrap_integer(TemplateFunction::new(property, move |commit| {
-                        num_char_in_id(commit, char_arg)
+                        num_char_in_id(commit, needle.clone())
+                    })),
+                )
+            },
+        );
+        table.commit_methods.insert(
+            "positions_of_char_in_id",
+            |language, _build_context, property, call| {
+                let [string_arg] = template_parser::expect_exact_arguments(call)?;
+                let needle = expect_nonempty_substring_arg(string_arg)?;
+
+                Ok(
+                    language.wrap_list(TemplateFunction::new(property, move |commit| {
+                        positions_of_char_in_id(commit, needle.clone())
                     })),
                 )
             },
@@ -127,12 +257,26 @@ impl CommitTemplateLanguageExtension for HexCounter {
         table
     }
 
+    #[instrument(skip_all)]
     fn build_cache_extensions(&self, extensions: &mut ExtensionsMap) {
         extensions.insert(MostDigitsInId::new());
     }
 }
 
+/// Installs a `tracing` subscriber gated behind `JJ_LOG`, since `CliRunner`
+/// doesn't install one itself. Example-local until it does.
+fn init_tracing() {
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::EnvFilter;
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(EnvFilter::try_from_env("JJ_LOG").unwrap_or_else(|_| EnvFilter::new("off")))
+        .init();
+}
+
 fn main() -> std::process::ExitCode {
+    init_tracing();
     CliRunner::init()
         .set_commit_template_extension(Box::new(HexCounter))
         .run()